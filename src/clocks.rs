@@ -0,0 +1,69 @@
+/**
+ * Copyright 2019 Benjamin Vaisvil
+ */
+use std::time::{Duration, Instant, SystemTime};
+
+/// Abstracts the passage of time so the sampling pipeline can be driven
+/// deterministically in tests, and so replay mode can scrub through
+/// recorded history instead of following the wall clock.
+pub trait Clocks {
+    /// Wall-clock time, used to timestamp recorded samples.
+    fn realtime(&self) -> SystemTime;
+    /// Monotonic time, used for measuring elapsed durations between ticks.
+    fn monotonic(&self) -> Instant;
+    /// Blocks the current thread for `dur`, the way `std::thread::sleep` would.
+    fn sleep(&self, dur: Duration);
+}
+
+/// The production `Clocks` implementation, backed by the real system clock.
+pub struct RealClocks;
+
+impl Clocks for RealClocks {
+    fn realtime(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn monotonic(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, dur: Duration) {
+        std::thread::sleep(dur)
+    }
+}
+
+/// A settable fake clock for tests: `realtime`/`monotonic` return whatever
+/// was last set via `advance`, and `sleep` just advances the fake clock
+/// instead of blocking.
+pub struct FakeClocks {
+    realtime: std::cell::RefCell<SystemTime>,
+    monotonic: std::cell::RefCell<Instant>,
+}
+
+impl FakeClocks {
+    pub fn new(start: SystemTime) -> FakeClocks {
+        FakeClocks {
+            realtime: std::cell::RefCell::new(start),
+            monotonic: std::cell::RefCell::new(Instant::now()),
+        }
+    }
+
+    pub fn advance(&self, dur: Duration) {
+        *self.realtime.borrow_mut() += dur;
+        *self.monotonic.borrow_mut() += dur;
+    }
+}
+
+impl Clocks for FakeClocks {
+    fn realtime(&self) -> SystemTime {
+        *self.realtime.borrow()
+    }
+
+    fn monotonic(&self) -> Instant {
+        *self.monotonic.borrow()
+    }
+
+    fn sleep(&self, dur: Duration) {
+        self.advance(dur);
+    }
+}
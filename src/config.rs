@@ -0,0 +1,95 @@
+/**
+ * Copyright 2019 Benjamin Vaisvil
+ */
+use crate::filter::{FilterError, NameFilter};
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Filters applied when enumerating disks in the disk widget.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct DiskConfig {
+    /// Glob/regex patterns. A disk is shown only if its device name matches.
+    pub name_filter: Option<Vec<String>>,
+    /// Glob/regex patterns. A disk is shown only if its mountpoint matches.
+    pub mount_filter: Option<Vec<String>>,
+}
+
+impl DiskConfig {
+    /// Compiles `name_filter`/`mount_filter` into matchers the disk widget
+    /// can apply while enumerating disks. A disk is shown when both the
+    /// name and the mountpoint matchers accept it.
+    pub fn compile(&self) -> Result<(NameFilter, NameFilter), FilterError> {
+        Ok((
+            NameFilter::compile(&self.name_filter)?,
+            NameFilter::compile(&self.mount_filter)?,
+        ))
+    }
+}
+
+/// Filters applied when enumerating network interfaces in the network widget.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct NetworkConfig {
+    /// Glob/regex patterns. An interface is shown only if its name matches.
+    pub interface_filter: Option<Vec<String>>,
+}
+
+impl NetworkConfig {
+    pub fn compile(&self) -> Result<NameFilter, FilterError> {
+        NameFilter::compile(&self.interface_filter)
+    }
+}
+
+/// Filters applied when enumerating hardware sensors in the temperature widget.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct TemperatureConfig {
+    /// Glob/regex patterns. A sensor is shown only if its label matches.
+    pub sensor_filter: Option<Vec<String>>,
+}
+
+impl TemperatureConfig {
+    pub fn compile(&self) -> Result<NameFilter, FilterError> {
+        NameFilter::compile(&self.sensor_filter)
+    }
+}
+
+/// Zenith's persisted configuration, merged with whatever was passed on the
+/// command line. CLI flags always win over the file, since they were
+/// supplied explicitly for this run.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct ZenithConfig {
+    pub refresh_rate: Option<u64>,
+    pub cpu_height: Option<u16>,
+    pub net_height: Option<u16>,
+    pub disk_height: Option<u16>,
+    pub process_height: Option<u16>,
+    pub sensor_height: Option<u16>,
+    #[serde(default)]
+    pub disk: DiskConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub temperature: TemperatureConfig,
+}
+
+impl ZenithConfig {
+    /// Returns the default location of the config file: `dirs::config_dir()/zenith/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|p| p.join("zenith").join("config.toml"))
+    }
+
+    /// Loads the config file at `path`, if it exists. Returns the default
+    /// (all-`None`/empty) config when no file is present, since a config
+    /// file is optional.
+    pub fn load(path: &Path) -> Result<ZenithConfig, Box<dyn Error>> {
+        if !path.exists() {
+            debug!("No config file at {}, using defaults.", path.display());
+            return Ok(ZenithConfig::default());
+        }
+        debug!("Loading config from {}", path.display());
+        let contents = fs::read_to_string(path)?;
+        let config: ZenithConfig = toml::from_str(&contents)?;
+        Ok(config)
+    }
+}
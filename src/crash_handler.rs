@@ -0,0 +1,88 @@
+/**
+ * Copyright 2019 Benjamin Vaisvil
+ */
+use backtrace::Backtrace;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A structured record of a panic, suitable for writing to disk or showing
+/// to the user once the terminal has been restored.
+pub struct CrashError {
+    pub timestamp: u64,
+    pub message: String,
+    pub location: String,
+    pub backtrace: Backtrace,
+}
+
+impl CrashError {
+    pub fn new(message: String, location: String) -> CrashError {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        CrashError {
+            timestamp,
+            message,
+            location,
+            backtrace: Backtrace::new(),
+        }
+    }
+
+    /// Path the report for this crash should be written to, rooted at `db_path`.
+    pub fn report_path(&self, db_path: &str) -> PathBuf {
+        Path::new(db_path).join(format!("crash-{}.log", self.timestamp))
+    }
+
+    /// Writes the plain-text (no ANSI) report to `report_path(db_path)` and
+    /// returns the path it was written to.
+    pub fn write_report(&self, db_path: &str) -> std::io::Result<PathBuf> {
+        let path = self.report_path(db_path);
+        let mut f = File::create(&path)?;
+        write!(f, "{:#}", self)?;
+        Ok(path)
+    }
+
+    /// A single colored line for the terminal -- the panic message and
+    /// location, with no backtrace. This is what the terminal actually
+    /// sees; the full `Display` dump is for the saved report file.
+    pub fn summary(&self) -> String {
+        format!(
+            "\x1b[31mthread panicked\x1b[0m at '\x1b[1m{}\x1b[0m', {}",
+            self.message, self.location
+        )
+    }
+}
+
+impl fmt::Display for CrashError {
+    /// The alternate form (`{:#}`) renders plain text for the crash log
+    /// file: no ANSI codes anywhere, including the backtrace. The normal
+    /// form is for terminal display: it colors the header *and* dims each
+    /// backtrace frame, so the two forms are genuinely different renders
+    /// of the same data rather than the color being cosmetic on the header
+    /// alone.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            writeln!(f, "zenith crash report")?;
+            writeln!(f, "timestamp: {}", self.timestamp)?;
+            writeln!(f, "panicked at '{}', {}", self.message, self.location)?;
+            writeln!(f, "\nbacktrace:")?;
+            write!(f, "{:?}", self.backtrace)
+        } else {
+            writeln!(f, "\x1b[31mzenith crash report\x1b[0m")?;
+            writeln!(f, "timestamp: {}", self.timestamp)?;
+            writeln!(
+                f,
+                "\x1b[1mpanicked at '{}', {}\x1b[0m",
+                self.message, self.location
+            )?;
+            writeln!(f, "\nbacktrace:")?;
+            for line in format!("{:?}", self.backtrace).lines() {
+                writeln!(f, "\x1b[2m{}\x1b[0m", line)?;
+            }
+            Ok(())
+        }
+    }
+}
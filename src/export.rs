@@ -0,0 +1,134 @@
+/**
+ * Copyright 2019 Benjamin Vaisvil
+ */
+use crate::metrics::history::HistorySample;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Output format for `--export`. Chosen explicitly via `--format`, or
+/// inferred from the export file's extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// Infers the format from a file extension (`.csv`/`.json`), falling
+    /// back to CSV when the extension is missing or unrecognized.
+    pub fn from_path(path: &Path) -> ExportFormat {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => ExportFormat::Json,
+            _ => ExportFormat::Csv,
+        }
+    }
+
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ExportFormat, String> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!("Unknown export format '{}', expected csv or json", s)),
+        }
+    }
+}
+
+/// Writes `records` to `out_path` in `format`, one row per sample.
+pub fn export_history(
+    records: &[HistorySample],
+    out_path: &Path,
+    format: ExportFormat,
+) -> Result<(), Box<dyn Error>> {
+    let file = File::create(out_path)?;
+    match format {
+        ExportFormat::Csv => export_csv(records, file),
+        ExportFormat::Json => export_json(records, file),
+    }
+}
+
+fn export_csv(records: &[HistorySample], mut file: File) -> Result<(), Box<dyn Error>> {
+    use std::io::Write;
+    writeln!(
+        file,
+        "timestamp,cpu_usage,memory_used,net_rx,net_tx,disk_available,disk_total,top_process"
+    )?;
+    for r in records {
+        let cpu_usage = r
+            .cpu_usage
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join("|");
+        let top_process = match &r.top_process {
+            Some((name, usage)) => format!("{}:{}", name, usage),
+            None => String::new(),
+        };
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{}",
+            r.timestamp,
+            cpu_usage,
+            r.memory_used,
+            format_pairs(&r.net_rx),
+            format_pairs(&r.net_tx),
+            format_pairs(&r.disk_available),
+            format_pairs(&r.disk_total),
+            top_process
+        )?;
+    }
+    Ok(())
+}
+
+/// Serializes `records` as a proper JSON array via `serde_json`, rather
+/// than hand-rolling JSON from `Debug` output (whose tuples and `Some(..)`
+/// wrappers aren't valid JSON).
+fn export_json(records: &[HistorySample], file: File) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(file, records)?;
+    Ok(())
+}
+
+fn format_pairs(pairs: &[(String, u64)]) -> String {
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{}:{}", name, value))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> HistorySample {
+        HistorySample {
+            timestamp: 42,
+            cpu_usage: vec![10.0, 20.0],
+            memory_used: 1024,
+            net_rx: vec![("eth0".to_string(), 100)],
+            net_tx: vec![("eth0".to_string(), 200)],
+            disk_available: vec![("sda1".to_string(), 300)],
+            disk_total: vec![("sda1".to_string(), 400)],
+            top_process: Some(("zenith".to_string(), 5.0)),
+        }
+    }
+
+    #[test]
+    fn json_export_round_trips_through_serde() {
+        let dir = std::env::temp_dir().join("zenith-export-test.json");
+        export_history(&[sample()], &dir, ExportFormat::Json).unwrap();
+
+        let contents = std::fs::read_to_string(&dir).unwrap();
+        let parsed: Vec<HistorySample> = serde_json::from_str(&contents).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].timestamp, 42);
+        assert_eq!(parsed[0].top_process, Some(("zenith".to_string(), 5.0)));
+
+        let _ = std::fs::remove_file(&dir);
+    }
+}
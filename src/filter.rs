@@ -0,0 +1,115 @@
+/**
+ * Copyright 2019 Benjamin Vaisvil
+ */
+use regex::Regex;
+use std::fmt;
+
+/// A compiled include-list: an entry is shown if it matches at least one of
+/// the configured patterns, or if no patterns were configured at all (in
+/// which case everything is shown, matching the current un-filtered
+/// behavior). Patterns are regexes; a glob like `docker*` is translated to
+/// the equivalent regex `^docker.*$` so users can write either.
+#[derive(Debug, Clone, Default)]
+pub struct NameFilter {
+    patterns: Vec<Regex>,
+}
+
+#[derive(Debug)]
+pub struct FilterError(String);
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid filter pattern: {}", self.0)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl NameFilter {
+    /// Compiles `patterns` into a `NameFilter`. A pattern is treated as a
+    /// glob (translated to a regex) if it contains `*` or `?` and doesn't
+    /// already look like a regex anchor/class; otherwise it's compiled as
+    /// a regex directly.
+    pub fn compile(patterns: &Option<Vec<String>>) -> Result<NameFilter, FilterError> {
+        let patterns = match patterns {
+            None => return Ok(NameFilter::default()),
+            Some(p) => p,
+        };
+        let mut compiled = Vec::with_capacity(patterns.len());
+        for p in patterns {
+            let pattern = if is_glob(p) { glob_to_regex(p) } else { p.clone() };
+            let re = Regex::new(&pattern).map_err(|e| FilterError(format!("{}: {}", p, e)))?;
+            compiled.push(re);
+        }
+        Ok(NameFilter { patterns: compiled })
+    }
+
+    /// Whether `candidate` should be shown: true if there are no configured
+    /// patterns, or `candidate` matches at least one of them.
+    pub fn matches(&self, candidate: &str) -> bool {
+        self.patterns.is_empty() || self.patterns.iter().any(|re| re.is_match(candidate))
+    }
+}
+
+fn is_glob(pattern: &str) -> bool {
+    (pattern.contains('*') || pattern.contains('?'))
+        && !pattern.contains('[')
+        && !pattern.starts_with('^')
+}
+
+fn glob_to_regex(pattern: &str) -> String {
+    let mut re = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            '.' | '(' | ')' | '+' | '|' | '^' | '$' | '[' | ']' | '{' | '}' | '\\' => {
+                re.push('\\');
+                re.push(c);
+            }
+            _ => re.push(c),
+        }
+    }
+    re.push('$');
+    re
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let f = NameFilter::compile(&None).unwrap();
+        assert!(f.matches("sda1"));
+        assert!(f.matches("anything"));
+    }
+
+    #[test]
+    fn regex_pattern_matches_only_listed_names() {
+        let f = NameFilter::compile(&Some(vec!["^/dev/sd.*".to_string()])).unwrap();
+        assert!(f.matches("/dev/sda1"));
+        assert!(!f.matches("/dev/loop0"));
+    }
+
+    #[test]
+    fn glob_pattern_is_translated_to_regex() {
+        let f = NameFilter::compile(&Some(vec!["eth*".to_string()])).unwrap();
+        assert!(f.matches("eth0"));
+        assert!(!f.matches("docker0"));
+    }
+
+    #[test]
+    fn any_pattern_matching_is_sufficient() {
+        let f = NameFilter::compile(&Some(vec!["eth*".to_string(), "wlan*".to_string()])).unwrap();
+        assert!(f.matches("eth0"));
+        assert!(f.matches("wlan0"));
+        assert!(!f.matches("docker0"));
+    }
+
+    #[test]
+    fn invalid_regex_is_rejected() {
+        let result = NameFilter::compile(&Some(vec!["(unclosed".to_string()]));
+        assert!(result.is_err());
+    }
+}
@@ -11,13 +11,22 @@ extern crate num_derive;
 extern crate log;
 
 
+mod clocks;
+mod config;
 mod constants;
+mod crash_handler;
+mod export;
+mod filter;
 mod metrics;
 mod render;
 mod util;
 mod zprocess;
 
 
+use crate::clocks::{Clocks, RealClocks};
+use crate::config::ZenithConfig;
+use crate::crash_handler::CrashError;
+use crate::export::{export_history, ExportFormat};
 use crate::render::TerminalRenderer;
 use clap::{App, Arg};
 use dirs;
@@ -28,8 +37,9 @@ use std::fs::{remove_file, File};
 use std::io::{Write, stdout};
 use std::panic;
 use std::panic::PanicInfo;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::str::FromStr;
 use termion::input::MouseTerminal;
 use termion::raw::IntoRawMode;
 use termion::screen::AlternateScreen;
@@ -37,7 +47,11 @@ use tui::backend::TermionBackend;
 use tui::Terminal;
 use env_logger;
 
-fn panic_hook(info: &PanicInfo<'_>) {
+fn panic_hook(info: &PanicInfo<'_>, db_path: &str, lock_path: Option<&Path>) {
+    // Restore the terminal first so the user isn't left staring at a
+    // corrupted alternate screen while we work out what happened.
+    restore_terminal();
+
     let location = info.location().unwrap(); // The current implementation always returns Some
     let msg = match info.payload().downcast_ref::<&'static str>() {
         Some(s) => *s,
@@ -46,16 +60,29 @@ fn panic_hook(info: &PanicInfo<'_>) {
             None => "Box<Any>",
         },
     };
-    error!(
-        "thread '<unnamed>' panicked at '{}', {}\r",
-        msg,
-        location);
-    println!(
-        "{}thread '<unnamed>' panicked at '{}', {}\r",
-        termion::screen::ToMainScreen,
-        msg,
-        location
-    );
+
+    // A copy of zenith that's about to go down shouldn't leave behind a
+    // stale lock that makes the next launch falsely report "already open".
+    if let Some(lock_path) = lock_path {
+        if lock_path.exists() {
+            let _ = remove_file(lock_path);
+        }
+    }
+
+    let crash = CrashError::new(msg.to_string(), location.to_string());
+    error!("{:#}", crash);
+
+    // The full report (with backtrace) goes to disk; the terminal only
+    // gets a one-line summary plus where to find it, so the user isn't
+    // left scrolling through a wall of text on a torn-down screen.
+    println!("{}\r", crash.summary());
+    match crash.write_report(db_path) {
+        Ok(path) => println!(
+            "A crash report was saved to {}. Please attach it to a bug report.\r",
+            path.display()
+        ),
+        Err(e) => println!("Could not write crash report: {}\r", e),
+    }
 }
 
 fn init_terminal(){
@@ -87,6 +114,7 @@ fn restore_terminal(){
 }
 
 fn start_zenith(
+    config: ZenithConfig,
     rate: u64,
     cpu_height: u16,
     net_height: u16,
@@ -95,21 +123,33 @@ fn start_zenith(
     sensor_height: u16,
     disable_history: bool,
     db_path: &str,
+    replay: bool,
 ) -> Result<(), Box<dyn Error>> {
 
-    debug!("Starting with Arguments: rate: {}, cpu: {}, net: {}, disk: {}, process: {}, disable_history: {}, db_path: {}",
+    debug!("Starting with Arguments: rate: {}, cpu: {}, net: {}, disk: {}, process: {}, disable_history: {}, db_path: {}, replay: {}",
           rate,
           cpu_height,
           net_height,
           disk_height,
           process_height,
           disable_history,
-          db_path
+          db_path,
+          replay
     );
 
     //check lock
     let lock_path = Path::new(db_path).join(Path::new(".zenith.lock"));
-    let db = if lock_path.exists() {
+    let db = if replay {
+        // Replay reads a pre-existing DB read-only, so it never takes the
+        // lock file -- multiple replay sessions, or a replay alongside a
+        // live zenith, can share the same DB path.
+        let p = Path::new(db_path);
+        if !p.exists() {
+            print!("{:} does not exist. Nothing to replay.", p.display());
+            exit(1);
+        }
+        Some(p.to_path_buf())
+    } else if lock_path.exists() {
         debug!("Lock exists.");
         if !disable_history {
             print!("{:} exists and history recording is on. Is another copy of zenith open? If not remove the path and open zenith again.", lock_path.display());
@@ -119,7 +159,7 @@ fn start_zenith(
         }
     } else {
         if !disable_history {
-            
+
             let p = Path::new(db_path);
             if !p.exists() {
                 debug!("Creating DB dir.");
@@ -136,11 +176,20 @@ fn start_zenith(
     init_terminal();
 
     // setup a panic hook so we can see our panic messages.
-    panic::set_hook(Box::new(|info| {
-        panic_hook(info);
+    let hook_db_path = db_path.to_string();
+    // Only a copy that created the lock removes it on panic; replay and
+    // history-disabled runs never own it.
+    let hook_lock_path = if replay || db.is_none() {
+        None
+    } else {
+        Some(lock_path.clone())
+    };
+    panic::set_hook(Box::new(move |info| {
+        panic_hook(info, &hook_db_path, hook_lock_path.as_deref());
     }));
 
     debug!("Create Renderer");
+    let clocks: Box<dyn Clocks> = Box::new(RealClocks);
     let mut r = TerminalRenderer::new(
         rate,
         cpu_height as i16,
@@ -149,12 +198,17 @@ fn start_zenith(
         process_height as i16,
         sensor_height as i16,
         db,
+        config.disk,
+        config.network,
+        config.temperature,
+        clocks,
+        replay,
     );
 
     let z = block_on(r.start());
-    
+
     debug!("Shutting Down.");
-    if !disable_history && lock_path.exists() {
+    if !replay && !disable_history && lock_path.exists() {
         debug!("Removing Lock");
         remove_file(lock_path)?
     }
@@ -164,6 +218,36 @@ fn start_zenith(
     Ok(z)
 }
 
+/// Reads the stored history for `db_path` in `[since, until]` (unix
+/// timestamps, inclusive) and writes it to `out_path` in `format`. Never
+/// takes `.zenith.lock`, since it only reads the database -- this mirrors
+/// the read-only behavior `--replay` needs.
+fn run_export(
+    db_path: &str,
+    out_path: &Path,
+    format: ExportFormat,
+    since: Option<u64>,
+    until: Option<u64>,
+) -> Result<(), Box<dyn Error>> {
+    debug!(
+        "Exporting history from {} to {} (since: {:?}, until: {:?})",
+        db_path,
+        out_path.display(),
+        since,
+        until
+    );
+    let records = metrics::history::read_range(Path::new(db_path), since, until)?;
+    export_history(&records, out_path, format)?;
+    println!("Exported {} records to {}", records.len(), out_path.display());
+    Ok(())
+}
+
+fn validate_timestamp(arg: String) -> Result<(), String> {
+    arg.parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| format!("{} is not a valid unix timestamp", &*arg))
+}
+
 fn validate_refresh_rate(arg: String) -> Result<(), String> {
     let val = arg.parse::<u64>().unwrap_or(0);
     if val >= 1000 {
@@ -188,6 +272,34 @@ fn validate_height(arg: String) -> Result<(), String> {
     }
 }
 
+const DEFAULT_REFRESH_RATE: u64 = 2000;
+const DEFAULT_CPU_HEIGHT: u16 = 10;
+const DEFAULT_NET_HEIGHT: u16 = 10;
+const DEFAULT_DISK_HEIGHT: u16 = 10;
+const DEFAULT_PROCESS_HEIGHT: u16 = 8;
+const DEFAULT_SENSOR_HEIGHT: u16 = 10;
+
+/// Resolves a CLI/config pair into a final value: the CLI flag wins when
+/// present, otherwise the config file value, otherwise `default`. The CLI
+/// value is already checked by clap's `.validator()`, but a config-file
+/// value is merged in straight from TOML, so it's run back through the
+/// same `validate` rule here -- otherwise an out-of-range config value
+/// (e.g. a refresh rate under 1000ms) would silently slip past the bar
+/// the CLI flag enforces.
+fn resolve<T: std::str::FromStr + ToString>(
+    cli: Option<&str>,
+    from_config: Option<T>,
+    default: T,
+    validate: fn(String) -> Result<(), String>,
+) -> Result<T, String> {
+    let value = match cli.map(|v| v.parse::<T>()) {
+        Some(Ok(v)) => v,
+        _ => from_config.unwrap_or(default),
+    };
+    validate(value.to_string())?;
+    Ok(value)
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let default_db_path = dirs::cache_dir()
         .unwrap_or(Path::new("./").to_owned())
@@ -195,6 +307,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let default_db_path = default_db_path
         .to_str()
         .expect("Couldn't set default db path");
+    let default_config_path = ZenithConfig::default_path();
     let matches = App::new("zenith")
         .version(env!("CARGO_PKG_VERSION"))
         .author("Benjamin Vaisvil <ben@neuon.com>")
@@ -204,12 +317,18 @@ Up/down arrow keys move around the process table. Return (enter) will focus on a
 Tab switches the active section. Active sections can be expanded (e) and minimized (m).
 Using this you can create the layout you want.",
         )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .help(format!("Config file to use. Defaults to {}/zenith/config.toml", dirs::config_dir().map(|p| p.display().to_string()).unwrap_or_default()).as_str())
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("refresh-rate")
                 .short("r")
                 .long("refresh-rate")
                 .value_name("INT")
-                .default_value("2000")
                 .validator(validate_refresh_rate)
                 .help(format!("Refresh rate in milliseconds.").as_str())
                 .takes_value(true),
@@ -219,7 +338,6 @@ Using this you can create the layout you want.",
                 .short("c")
                 .long("cpu-height")
                 .value_name("INT")
-                .default_value("10")
                 .validator(validate_height)
                 .help(format!("Height of CPU/Memory visualization.").as_str())
                 .takes_value(true),
@@ -229,7 +347,6 @@ Using this you can create the layout you want.",
                 .short("n")
                 .long("net-height")
                 .value_name("INT")
-                .default_value("10")
                 .validator(validate_height)
                 .help(format!("Height of Network visualization.").as_str())
                 .takes_value(true),
@@ -239,27 +356,24 @@ Using this you can create the layout you want.",
                 .short("d")
                 .long("disk-height")
                 .value_name("INT")
-                .default_value("10")
                 .validator(validate_height)
                 .help(format!("Height of Disk visualization.").as_str())
                 .takes_value(true),
         )
-        // .arg(
-        //     Arg::with_name("sensor-height")
-        //         .short("s")
-        //         .long("sensor-height")
-        //         .value_name("INT")
-        //         .default_value("10")
-        //         .validator(validate_height)
-        //         .help(format!("Height of Sensor visualization.").as_str())
-        //         .takes_value(true),
-        // )
+        .arg(
+            Arg::with_name("sensor-height")
+                .short("s")
+                .long("sensor-height")
+                .value_name("INT")
+                .validator(validate_height)
+                .help(format!("Height of Sensor visualization.").as_str())
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("process-height")
                 .short("p")
                 .long("process-height")
                 .value_name("INT")
-                .default_value("8")
                 .validator(validate_height)
                 .help(format!("Min Height of Process Table.").as_str())
                 .takes_value(true),
@@ -278,39 +392,113 @@ Using this you can create the layout you want.",
                 .help(format!("Database to use, if any.").as_str())
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("replay")
+                .long("replay")
+                .help(format!("Replay history from the database at --db instead of showing live data. Use the arrow keys to scrub backward/forward.").as_str())
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("export")
+                .long("export")
+                .value_name("FILE")
+                .help(format!("Export history from the database at --db to FILE instead of showing the UI. Format is inferred from the extension unless --format is given.").as_str())
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("csv|json")
+                .requires("export")
+                .help(format!("Export format. Overrides the extension of --export.").as_str())
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("since")
+                .long("since")
+                .value_name("UNIX_TIMESTAMP")
+                .requires("export")
+                .validator(validate_timestamp)
+                .help(format!("Only export samples recorded at or after this time.").as_str())
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("until")
+                .long("until")
+                .value_name("UNIX_TIMESTAMP")
+                .requires("export")
+                .validator(validate_timestamp)
+                .help(format!("Only export samples recorded at or before this time.").as_str())
+                .takes_value(true),
+        )
         .get_matches();
 
     env_logger::init();
     info!("Starting zenith {}", env!("CARGO_PKG_VERSION"));
 
+    let config_path: PathBuf = match matches.value_of("config") {
+        Some(p) => PathBuf::from(p),
+        None => default_config_path.unwrap_or(PathBuf::from("zenith-config.toml")),
+    };
+    let config = ZenithConfig::load(&config_path)?;
+
+    if let Some(export_path) = matches.value_of("export") {
+        let out_path = Path::new(export_path);
+        let format = match matches.value_of("format") {
+            Some(f) => ExportFormat::from_str(f)?,
+            None => ExportFormat::from_path(out_path),
+        };
+        let since = matches.value_of("since").map(|v| v.parse::<u64>().unwrap());
+        let until = matches.value_of("until").map(|v| v.parse::<u64>().unwrap());
+        return run_export(
+            matches.value_of("db").unwrap(),
+            out_path,
+            format,
+            since,
+            until,
+        );
+    }
+
     start_zenith(
-        matches
-            .value_of("refresh-rate")
-            .unwrap()
-            .parse::<u64>()
-            .unwrap(),
-        matches
-            .value_of("cpu-height")
-            .unwrap()
-            .parse::<u16>()
-            .unwrap(),
-        matches
-            .value_of("net-height")
-            .unwrap()
-            .parse::<u16>()
-            .unwrap(),
-        matches
-            .value_of("disk-height")
-            .unwrap()
-            .parse::<u16>()
-            .unwrap(),
-        matches
-            .value_of("process-height")
-            .unwrap()
-            .parse::<u16>()
-            .unwrap(),
-        0,
+        config.clone(),
+        resolve(
+            matches.value_of("refresh-rate"),
+            config.refresh_rate,
+            DEFAULT_REFRESH_RATE,
+            validate_refresh_rate,
+        )?,
+        resolve(
+            matches.value_of("cpu-height"),
+            config.cpu_height,
+            DEFAULT_CPU_HEIGHT,
+            validate_height,
+        )?,
+        resolve(
+            matches.value_of("net-height"),
+            config.net_height,
+            DEFAULT_NET_HEIGHT,
+            validate_height,
+        )?,
+        resolve(
+            matches.value_of("disk-height"),
+            config.disk_height,
+            DEFAULT_DISK_HEIGHT,
+            validate_height,
+        )?,
+        resolve(
+            matches.value_of("process-height"),
+            config.process_height,
+            DEFAULT_PROCESS_HEIGHT,
+            validate_height,
+        )?,
+        resolve(
+            matches.value_of("sensor-height"),
+            config.sensor_height,
+            DEFAULT_SENSOR_HEIGHT,
+            validate_height,
+        )?,
         matches.is_present("disable-history"),
         matches.value_of("db").unwrap(),
+        matches.is_present("replay"),
     )
 }
@@ -0,0 +1,180 @@
+/**
+ * Copyright 2019 Benjamin Vaisvil
+ */
+pub mod history {
+    use crate::clocks::Clocks;
+    use serde::{Deserialize, Serialize};
+    use std::error::Error;
+    use std::fs::OpenOptions;
+    use std::io::{BufRead, BufReader, Write};
+    use std::path::Path;
+    use std::time::SystemTime;
+    use sysinfo::{ComponentExt, DiskExt, NetworkExt, ProcessExt, System, SystemExt};
+
+    /// One row of recorded history, persisted as a line of JSON in
+    /// `db_path/history.jsonl` and read back for `--replay` and `--export`.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct HistorySample {
+        pub timestamp: u64,
+        pub cpu_usage: Vec<f32>,
+        pub memory_used: u64,
+        pub net_rx: Vec<(String, u64)>,
+        pub net_tx: Vec<(String, u64)>,
+        /// Free space per disk, in bytes. sysinfo's `Disk` type only
+        /// reports capacity, not I/O throughput, so this (and
+        /// `disk_total`) are point-in-time space figures rather than
+        /// read/write rates.
+        pub disk_available: Vec<(String, u64)>,
+        /// Total space per disk, in bytes.
+        pub disk_total: Vec<(String, u64)>,
+        pub top_process: Option<(String, f32)>,
+    }
+
+    fn history_path(db_path: &Path) -> std::path::PathBuf {
+        db_path.join("history.jsonl")
+    }
+
+    /// Builds a `HistorySample` from the current state of `system`, using
+    /// `clocks.realtime()` (rather than `SystemTime::now()` directly) to
+    /// timestamp it. This is what makes the sampling pipeline
+    /// deterministically testable with a `FakeClocks`.
+    pub fn sample_now(clocks: &dyn Clocks, system: &System) -> HistorySample {
+        let timestamp = clocks
+            .realtime()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let cpu_usage = system.processors().iter().map(|p| p.cpu_usage()).collect();
+        let memory_used = system.used_memory();
+        let net_rx = system
+            .networks()
+            .iter()
+            .map(|(name, data)| (name.clone(), data.received()))
+            .collect();
+        let net_tx = system
+            .networks()
+            .iter()
+            .map(|(name, data)| (name.clone(), data.transmitted()))
+            .collect();
+        let disk_available = system
+            .disks()
+            .iter()
+            .map(|d| {
+                (
+                    d.name().to_string_lossy().to_string(),
+                    d.available_space(),
+                )
+            })
+            .collect();
+        let disk_total = system
+            .disks()
+            .iter()
+            .map(|d| (d.name().to_string_lossy().to_string(), d.total_space()))
+            .collect();
+        let top_process = system
+            .processes()
+            .values()
+            .max_by(|a, b| {
+                a.cpu_usage()
+                    .partial_cmp(&b.cpu_usage())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|p| (p.name().to_string(), p.cpu_usage()));
+
+        HistorySample {
+            timestamp,
+            cpu_usage,
+            memory_used,
+            net_rx,
+            net_tx,
+            disk_available,
+            disk_total,
+            top_process,
+        }
+    }
+
+    /// Appends `sample` as a line of JSON to `db_path/history.jsonl`.
+    pub fn append(db_path: &Path, sample: &HistorySample) -> Result<(), Box<dyn Error>> {
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(history_path(db_path))?;
+        writeln!(f, "{}", serde_json::to_string(sample)?)?;
+        Ok(())
+    }
+
+    /// Reads every recorded sample in `[since, until]` (unix timestamps,
+    /// inclusive), in recording order.
+    pub fn read_range(
+        db_path: &Path,
+        since: Option<u64>,
+        until: Option<u64>,
+    ) -> Result<Vec<HistorySample>, Box<dyn Error>> {
+        let path = history_path(db_path);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let f = std::fs::File::open(path)?;
+        let mut samples = Vec::new();
+        for line in BufReader::new(f).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let sample: HistorySample = serde_json::from_str(&line)?;
+            if since.map_or(true, |s| sample.timestamp >= s)
+                && until.map_or(true, |u| sample.timestamp <= u)
+            {
+                samples.push(sample);
+            }
+        }
+        Ok(samples)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::clocks::FakeClocks;
+        use std::time::Duration;
+
+        #[test]
+        fn sample_now_uses_the_injected_clock_not_the_system_clock() {
+            let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+            let clocks = FakeClocks::new(start);
+            let system = System::new();
+
+            let sample = sample_now(&clocks, &system);
+            assert_eq!(sample.timestamp, 1_000_000);
+
+            clocks.advance(Duration::from_secs(60));
+            let sample = sample_now(&clocks, &system);
+            assert_eq!(sample.timestamp, 1_000_060);
+        }
+
+        #[test]
+        fn read_range_filters_by_timestamp() {
+            let dir = std::env::temp_dir().join(format!(
+                "zenith-metrics-test-{:?}",
+                std::thread::current().id()
+            ));
+            let _ = std::fs::create_dir_all(&dir);
+            let system = System::new();
+            let clocks = FakeClocks::new(SystemTime::UNIX_EPOCH + Duration::from_secs(100));
+            append(&dir, &sample_now(&clocks, &system)).unwrap();
+            clocks.advance(Duration::from_secs(100));
+            append(&dir, &sample_now(&clocks, &system)).unwrap();
+            clocks.advance(Duration::from_secs(100));
+            append(&dir, &sample_now(&clocks, &system)).unwrap();
+
+            let all = read_range(&dir, None, None).unwrap();
+            assert_eq!(all.len(), 3);
+
+            let sliced = read_range(&dir, Some(150), Some(250)).unwrap();
+            assert_eq!(sliced.len(), 1);
+            assert_eq!(sliced[0].timestamp, 200);
+
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+    }
+}
@@ -0,0 +1,444 @@
+/**
+ * Copyright 2019 Benjamin Vaisvil
+ */
+use crate::clocks::Clocks;
+use crate::config::{DiskConfig, NetworkConfig, TemperatureConfig};
+use crate::filter::NameFilter;
+use crate::metrics::history::{self, HistorySample};
+use std::collections::VecDeque;
+use std::io::stdout;
+use std::path::PathBuf;
+use std::time::Duration;
+use sysinfo::{ComponentExt, System, SystemExt};
+use termion::event::Key;
+use termion::input::{MouseTerminal, TermRead};
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use tui::backend::TermionBackend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Paragraph};
+use tui::{Frame, Terminal};
+
+/// How many past readings are kept per sensor. The sensor panel draws only
+/// the latest one.
+const SENSOR_HISTORY_LEN: usize = 60;
+
+/// A single hardware temperature sensor (from sysinfo's component API),
+/// tracked over time the same way CPU/net histories are tracked.
+struct SensorHistory {
+    label: String,
+    readings: VecDeque<f32>,
+    critical: Option<f32>,
+}
+
+impl SensorHistory {
+    fn new(label: String, critical: Option<f32>) -> SensorHistory {
+        SensorHistory {
+            label,
+            readings: VecDeque::with_capacity(SENSOR_HISTORY_LEN),
+            critical,
+        }
+    }
+
+    fn push(&mut self, reading: f32) {
+        if self.readings.len() == SENSOR_HISTORY_LEN {
+            self.readings.pop_front();
+        }
+        self.readings.push_back(reading);
+    }
+
+    fn current(&self) -> f32 {
+        *self.readings.back().unwrap_or(&0.0)
+    }
+
+    /// A sensor is "hot" once its current reading is at or above its
+    /// critical threshold (when the hardware reports one).
+    fn is_critical(&self) -> bool {
+        match self.critical {
+            Some(c) => self.current() >= c,
+            None => false,
+        }
+    }
+}
+
+pub struct TerminalRenderer {
+    rate: u64,
+    cpu_height: i16,
+    net_height: i16,
+    disk_height: i16,
+    process_height: i16,
+    sensor_height: i16,
+    db: Option<PathBuf>,
+    disk_name_filter: NameFilter,
+    disk_mount_filter: NameFilter,
+    network_filter: NameFilter,
+    sensor_filter: NameFilter,
+    clocks: Box<dyn Clocks>,
+    replay: bool,
+    system: System,
+    sensors: Vec<SensorHistory>,
+    disk_names: Vec<String>,
+    interface_names: Vec<String>,
+    replay_samples: Vec<HistorySample>,
+    replay_cursor: usize,
+}
+
+impl TerminalRenderer {
+    pub fn new(
+        rate: u64,
+        cpu_height: i16,
+        net_height: i16,
+        disk_height: i16,
+        process_height: i16,
+        sensor_height: i16,
+        db: Option<PathBuf>,
+        disk_config: DiskConfig,
+        network_config: NetworkConfig,
+        temperature_config: TemperatureConfig,
+        clocks: Box<dyn Clocks>,
+        replay: bool,
+    ) -> TerminalRenderer {
+        let (disk_name_filter, disk_mount_filter) = disk_config
+            .compile()
+            .expect("Invalid disk filter pattern in config.");
+        let network_filter = network_config
+            .compile()
+            .expect("Invalid network filter pattern in config.");
+        let sensor_filter = temperature_config
+            .compile()
+            .expect("Invalid sensor filter pattern in config.");
+
+        TerminalRenderer {
+            rate,
+            cpu_height,
+            net_height,
+            disk_height,
+            process_height,
+            sensor_height,
+            db,
+            disk_name_filter,
+            disk_mount_filter,
+            network_filter,
+            sensor_filter,
+            clocks,
+            replay,
+            system: System::new_all(),
+            sensors: Vec::new(),
+            disk_names: Vec::new(),
+            interface_names: Vec::new(),
+            replay_samples: Vec::new(),
+            replay_cursor: 0,
+        }
+    }
+
+    /// Re-reads the hardware components and updates each matching sensor's
+    /// history, adding newly-seen sensors as they appear. This is the
+    /// "tracked over time" counterpart to the CPU/net history buffers.
+    fn update_sensors(&mut self) {
+        self.system.refresh_components_list();
+        self.system.refresh_components();
+
+        for component in self.system.components() {
+            let label = component.label().to_string();
+            if !self.sensor_filter.matches(&label) {
+                continue;
+            }
+            let reading = component.temperature();
+            match self.sensors.iter_mut().find(|s| s.label == label) {
+                Some(existing) => existing.push(reading),
+                None => {
+                    let mut history = SensorHistory::new(label, component.critical());
+                    history.push(reading);
+                    self.sensors.push(history);
+                }
+            }
+        }
+    }
+
+    /// Refreshes the disk list and records the names of the disks that pass
+    /// both the name and mountpoint filters, so `draw()` has something to
+    /// show for the `[disk]` config filters.
+    fn filtered_disk_names(&mut self) {
+        self.system.refresh_disks_list();
+        self.system.refresh_disks();
+        self.disk_names = self
+            .system
+            .disks()
+            .iter()
+            .filter(|d| {
+                let name = d.name().to_string_lossy();
+                let mount = d.mount_point().to_string_lossy();
+                self.disk_name_filter.matches(&name) && self.disk_mount_filter.matches(&mount)
+            })
+            .map(|d| d.name().to_string_lossy().to_string())
+            .collect();
+    }
+
+    /// Refreshes the network interface list and records the names of the
+    /// interfaces that pass the `[network]` interface filter.
+    fn filtered_interface_names(&mut self) {
+        self.system.refresh_networks_list();
+        self.system.refresh_networks();
+        self.interface_names = self
+            .system
+            .networks()
+            .iter()
+            .filter(|(name, _)| self.network_filter.matches(name))
+            .map(|(name, _)| name.clone())
+            .collect();
+    }
+
+    /// Renders the sensor panel: one bar per tracked sensor, filled by its
+    /// current reading on a 0-100C scale and highlighted red once it's at or
+    /// above its critical threshold. Past readings are not drawn.
+    fn render_sensor_panel<B: tui::backend::Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let block = Block::default().title("Sensors").borders(Borders::ALL);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let lines: Vec<Spans> = self
+            .sensors
+            .iter()
+            .map(|s| {
+                let style = if s.is_critical() {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default().fg(Color::Green)
+                };
+                let bar_width = inner.width.saturating_sub(24) as usize;
+                let filled = ((s.current() / 100.0).min(1.0).max(0.0) * bar_width as f32) as usize;
+                let bar: String = "#".repeat(filled) + &" ".repeat(bar_width.saturating_sub(filled));
+                Spans::from(vec![Span::styled(
+                    format!("{:<14} {:>5.1}C [{}]", s.label, s.current(), bar),
+                    style,
+                )])
+            })
+            .collect();
+
+        f.render_widget(Paragraph::new(lines), inner);
+    }
+
+    /// Renders a simple newline-per-entry list panel, used for the disk and
+    /// network panels when showing which names passed their filter.
+    fn render_name_list<B: tui::backend::Backend>(
+        title: String,
+        names: &[String],
+        f: &mut Frame<B>,
+        area: Rect,
+    ) {
+        let block = Block::default().title(title).borders(Borders::ALL);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let lines: Vec<Spans> = names.iter().map(|n| Spans::from(n.as_str())).collect();
+        f.render_widget(Paragraph::new(lines), inner);
+    }
+
+    /// Renders a panel of "name: value" lines, used for replay's CPU/net/disk
+    /// panels where the data to show comes from a recorded `HistorySample`
+    /// rather than the live `System`.
+    fn render_text_panel<B: tui::backend::Backend>(
+        title: String,
+        lines: Vec<String>,
+        f: &mut Frame<B>,
+        area: Rect,
+    ) {
+        let block = Block::default().title(title).borders(Borders::ALL);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let spans: Vec<Spans> = lines.iter().map(|l| Spans::from(l.as_str())).collect();
+        f.render_widget(Paragraph::new(spans), inner);
+    }
+
+    fn draw<B: tui::backend::Backend>(&self, f: &mut Frame<B>) {
+        let size = f.size();
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(self.cpu_height.max(0) as u16),
+                    Constraint::Length(self.net_height.max(0) as u16),
+                    Constraint::Length(self.disk_height.max(0) as u16),
+                    Constraint::Length(self.sensor_height.max(0) as u16),
+                    Constraint::Min(self.process_height.max(0) as u16),
+                ]
+                .as_ref(),
+            )
+            .split(size);
+
+        let sample = if self.replay {
+            self.replay_samples.get(self.replay_cursor)
+        } else {
+            None
+        };
+        let title = |base: &str| match sample {
+            Some(s) => format!(
+                "{} (replay {}/{} @ t={})",
+                base,
+                self.replay_cursor + 1,
+                self.replay_samples.len(),
+                s.timestamp
+            ),
+            None => base.to_string(),
+        };
+
+        match sample {
+            Some(s) => {
+                let cpu_lines = s
+                    .cpu_usage
+                    .iter()
+                    .enumerate()
+                    .map(|(i, usage)| format!("cpu{:<3} {:>5.1}%", i, usage))
+                    .collect();
+                TerminalRenderer::render_text_panel(title("CPU"), cpu_lines, f, chunks[0]);
+
+                let net_lines = s
+                    .net_rx
+                    .iter()
+                    .map(|(name, rx)| {
+                        let tx = s
+                            .net_tx
+                            .iter()
+                            .find(|(n, _)| n == name)
+                            .map(|(_, v)| *v)
+                            .unwrap_or(0);
+                        format!("{:<10} rx {:>10} tx {:>10}", name, rx, tx)
+                    })
+                    .collect();
+                TerminalRenderer::render_text_panel(title("Network"), net_lines, f, chunks[1]);
+
+                let disk_lines = s
+                    .disk_available
+                    .iter()
+                    .map(|(name, available)| {
+                        let total = s
+                            .disk_total
+                            .iter()
+                            .find(|(n, _)| n == name)
+                            .map(|(_, v)| *v)
+                            .unwrap_or(0);
+                        format!("{:<10} {:>10} / {:>10}", name, available, total)
+                    })
+                    .collect();
+                TerminalRenderer::render_text_panel(title("Disk"), disk_lines, f, chunks[2]);
+            }
+            None => {
+                f.render_widget(Block::default().title(title("CPU")).borders(Borders::ALL), chunks[0]);
+                TerminalRenderer::render_name_list(title("Network"), &self.interface_names, f, chunks[1]);
+                TerminalRenderer::render_name_list(title("Disk"), &self.disk_names, f, chunks[2]);
+            }
+        }
+        self.render_sensor_panel(f, chunks[3]);
+        match sample {
+            Some(s) => {
+                let process_lines = match &s.top_process {
+                    Some((name, usage)) => vec![format!("top: {} ({:.1}%)", name, usage)],
+                    None => vec!["top: n/a".to_string()],
+                };
+                TerminalRenderer::render_text_panel(title("Process"), process_lines, f, chunks[4]);
+            }
+            None => {
+                f.render_widget(
+                    Block::default().title(title("Process")).borders(Borders::ALL),
+                    chunks[4],
+                );
+            }
+        }
+    }
+
+    pub async fn start(&mut self) {
+        if self.replay {
+            let db = self
+                .db
+                .clone()
+                .expect("Replay mode requires --db to point at an existing database.");
+            self.replay_samples = history::read_range(&db, None, None).unwrap_or_default();
+            self.replay_cursor = self.replay_samples.len().saturating_sub(1);
+        }
+
+        let stdout = stdout().into_raw_mode().expect("Could not enter raw mode.");
+        let stdout = MouseTerminal::from(stdout);
+        let stdout = AlternateScreen::from(stdout);
+        let backend = TermionBackend::new(stdout);
+        let mut terminal = Terminal::new(backend).expect("Could not create terminal.");
+
+        let mut keys = termion::async_stdin().keys();
+        let rate = Duration::from_millis(self.rate);
+
+        loop {
+            let tick_start = self.clocks.monotonic();
+
+            if !self.replay {
+                self.system.refresh_cpu();
+                self.system.refresh_processes();
+                self.update_sensors();
+                self.filtered_disk_names();
+                self.filtered_interface_names();
+                if let Some(db) = self.db.clone() {
+                    let sample = history::sample_now(self.clocks.as_ref(), &self.system);
+                    let _ = history::append(&db, &sample);
+                }
+            }
+
+            terminal.draw(|f| self.draw(f)).ok();
+
+            let mut quit = false;
+            while let Some(Ok(key)) = keys.next() {
+                match key {
+                    Key::Char('q') | Key::Esc => quit = true,
+                    Key::Left if self.replay => {
+                        self.replay_cursor = self.replay_cursor.saturating_sub(1)
+                    }
+                    Key::Right if self.replay => {
+                        self.replay_cursor = (self.replay_cursor + 1)
+                            .min(self.replay_samples.len().saturating_sub(1))
+                    }
+                    _ => {}
+                }
+            }
+            if quit {
+                break;
+            }
+
+            // Driving the tick cadence off `self.clocks` (rather than
+            // `std::thread::sleep` directly) is what lets a `FakeClocks`
+            // drive this loop deterministically in tests.
+            let elapsed = self.clocks.monotonic().duration_since(tick_start);
+            if elapsed < rate {
+                self.clocks.sleep(rate - elapsed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sensor_history_flags_readings_at_or_above_critical() {
+        let mut h = SensorHistory::new("coretemp".to_string(), Some(90.0));
+        h.push(70.0);
+        assert!(!h.is_critical());
+        h.push(95.0);
+        assert!(h.is_critical());
+    }
+
+    #[test]
+    fn sensor_history_is_not_critical_without_a_threshold() {
+        let mut h = SensorHistory::new("coretemp".to_string(), None);
+        h.push(200.0);
+        assert!(!h.is_critical());
+    }
+
+    #[test]
+    fn sensor_history_caps_at_fixed_length() {
+        let mut h = SensorHistory::new("coretemp".to_string(), None);
+        for i in 0..(SENSOR_HISTORY_LEN + 10) {
+            h.push(i as f32);
+        }
+        assert_eq!(h.readings.len(), SENSOR_HISTORY_LEN);
+        assert_eq!(h.current(), (SENSOR_HISTORY_LEN + 9) as f32);
+    }
+}